@@ -9,23 +9,38 @@
 
 use core::{
     future::Future,
+    marker::PhantomData,
     pin::Pin,
     task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
 };
 
+#[cfg(feature = "stream")]
+use futures_core::Stream;
+
 #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
 use std::{
-    cell::RefCell,
+    cell::UnsafeCell,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Condvar, Mutex,
     },
 };
 
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(not(feature = "std"))]
+use alloc::sync::Arc;
+
 #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
 use alloc::{boxed::Box, vec::Vec};
 #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
-use core::{any::Any, cell::RefCell, marker::PhantomData};
+use core::{any::Any, cell::RefCell};
+
+#[cfg(not(feature = "std"))]
+use core::{
+    cell::Cell,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
 // Either a Future or Output or Empty
 #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
@@ -44,27 +59,205 @@ impl Task {
     }
 }
 
-// Executor data.
-struct Exec {
-    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
-    // The thread-safe waking mechanism: part 1
-    mutex: Mutex<()>,
+// States for `AtomicWaker`'s single-slot registration.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+const WAITING: usize = 0;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+const REGISTERING: usize = 1;
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+const WAKING: usize = 2;
 
-    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
-    // The thread-safe waking mechanism: part 2
-    cvar: Condvar,
+// A lock-free single-slot `Waker` registration, replacing the
+// `Mutex`-guarded waker that `JoinHandle` used to store.  Modeled after
+// `futures`' `AtomicWaker`.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
 
-    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
-    // Flag set to verify `Condvar` actually woke the executor.
-    state: AtomicBool,
+// Safety: access to `waker` is guarded by `state`'s CAS protocol below, so
+// only one side ever touches it at a time.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[allow(unsafe_code)]
+unsafe impl Send for AtomicWaker {}
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[allow(unsafe_code)]
+unsafe impl Sync for AtomicWaker {}
 
-    #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
-    // Pinned future.
-    tasks: RefCell<Vec<Task>>,
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl AtomicWaker {
+    fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    // Register `waker` to be woken by the next call to `wake()`.
+    #[allow(unsafe_code)]
+    fn register(&self, waker: &Waker) {
+        match self.state.compare_exchange(
+            WAITING,
+            REGISTERING,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => {
+                // Safety: holding the `REGISTERING` state gives exclusive
+                // access to `waker`.
+                unsafe {
+                    *self.waker.get() = Some(waker.clone());
+                }
+
+                let previous = self.state.compare_exchange(
+                    REGISTERING,
+                    WAITING,
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                );
+
+                if previous.is_err() {
+                    // `wake()` raced with this registration and observed
+                    // `REGISTERING`; it left us to wake the waker we just
+                    // stored, since it had nothing to take.
+                    // Safety: still exclusive access to `waker` until the
+                    // state below is reset to `WAITING`.
+                    let waker = unsafe { (*self.waker.get()).take() };
+
+                    self.state.store(WAITING, Ordering::Release);
+
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            // A wake arrived before we could register; wake immediately
+            // instead of storing a waker that might never be woken.
+            Err(WAKING) => waker.wake_by_ref(),
+            // Another registration is already in flight; let it win.
+            Err(_) => {}
+        }
+    }
+
+    // Wake the registered waker, if any, or mark that a wake is pending so
+    // the in-flight `register()` wakes on its own.
+    fn wake(&self) {
+        match self.state.swap(WAKING, Ordering::AcqRel) {
+            WAITING => {
+                // Safety: `WAITING` means no `register()` is in flight, so
+                // `waker` is ours to take.
+                #[allow(unsafe_code)]
+                let waker = unsafe { (*self.waker.get()).take() };
+
+                self.state.store(WAITING, Ordering::Release);
+
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }
+            // Either already woken, or an in-flight `register()` will
+            // notice the `WAKING` state and wake itself.
+            WAKING | REGISTERING => {}
+            _ => unreachable!(),
+        }
+    }
 }
 
-impl Exec {
-    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+// Hand-written because `UnsafeCell<Option<Waker>>` isn't `Debug`, and this
+// type is stored behind an `Arc` in `#[derive(Debug)]` types (`JoinHandle`,
+// `ScopedJoinHandle`); the inner waker isn't meaningful to display anyway.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl core::fmt::Debug for AtomicWaker {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AtomicWaker").finish_non_exhaustive()
+    }
+}
+
+// A lock-free single-slot handoff for a task's output, replacing the
+// `Mutex`-guarded `Option<T>` that `JoinHandle`/`ScopedJoinHandle` used to
+// store. Unlike `AtomicWaker`, there's no registration race to arbitrate:
+// the worker thread writes exactly once, and the owning handle is the only
+// reader, so a single `ready` flag is enough to hand the value across
+// threads without taking a lock on the poll/cancel hot path.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+struct OutputSlot<T> {
+    ready: AtomicBool,
+    value: UnsafeCell<Option<T>>,
+}
+
+// Safety: `value` is written once by the worker thread before `ready` is
+// set (release), and only read after observing `ready` (acquire), so the
+// write always happens-before the read; the owning handle is the only
+// reader, so `take()` is never called concurrently with itself.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[allow(unsafe_code)]
+unsafe impl<T: Send> Send for OutputSlot<T> {}
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[allow(unsafe_code)]
+unsafe impl<T: Send> Sync for OutputSlot<T> {}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl<T> OutputSlot<T> {
+    fn new() -> Self {
+        Self {
+            ready: AtomicBool::new(false),
+            value: UnsafeCell::new(None),
+        }
+    }
+
+    // Called once, by the worker thread, after the task finishes.
+    #[allow(unsafe_code)]
+    fn set(&self, value: T) {
+        // Safety: called once, before `ready` is published below, so no
+        // reader can be looking at `value` yet.
+        unsafe {
+            *self.value.get() = Some(value);
+        }
+        self.ready.store(true, Ordering::Release);
+    }
+
+    // Take the value if the worker has finished; safe to call more than
+    // once (e.g. once before registering a waker and again after), since
+    // after the first successful take it keeps observing `None`.
+    #[allow(unsafe_code)]
+    fn take(&self) -> Option<T> {
+        if self.ready.load(Ordering::Acquire) {
+            // Safety: `ready` was just observed `true`, so the worker's
+            // write to `value` has happened-before this read.
+            unsafe { (*self.value.get()).take() }
+        } else {
+            None
+        }
+    }
+}
+
+// Hand-written for the same reason as `AtomicWaker`'s `Debug` impl: the
+// `UnsafeCell` isn't `Debug`, and the contained value isn't meaningful to
+// display before it's been taken.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl<T> core::fmt::Debug for OutputSlot<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("OutputSlot").finish_non_exhaustive()
+    }
+}
+
+// The thread-safe waking mechanism for the std, thread-per-task executor.
+// Shared via `Arc` between a spawned task's thread and its `JoinHandle`, so
+// unlike `Exec` (which lives in the owning thread's thread-local storage and
+// is torn down when that thread exits), a `Waker` built from a `Park` stays
+// valid for as long as anything holds the `Arc`, including after the task's
+// thread has finished and gone away.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+struct Park {
+    mutex: Mutex<()>,
+    cvar: Condvar,
+    state: AtomicBool,
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl Park {
     fn new() -> Self {
         Self {
             mutex: Mutex::new(()),
@@ -73,26 +266,108 @@ impl Exec {
         }
     }
 
-    #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
-    fn new() -> Self {
-        Self {
-            tasks: RefCell::new(Vec::new()),
+    // Put the calling thread to sleep until `wake_by_ref()` is called.
+    fn park(&self) {
+        let mut guard = self.mutex.lock().unwrap();
+        while !self.state.compare_and_swap(true, false, Ordering::SeqCst) {
+            guard = self.cvar.wait(guard).unwrap();
         }
     }
+}
 
-    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
-    fn wake(&self) {
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl Wake for Park {
+    fn wake_by_ref(self: &Arc<Self>) {
         // Wake the task running on a separate thread via CondVar
         if !self.state.compare_and_swap(false, true, Ordering::SeqCst) {
             // We notify the condvar that the value has changed.
             self.cvar.notify_one();
         }
     }
+}
 
-    #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
+// Run `f` to completion on the calling thread, unless `cancelled` is set
+// first. `park` both drives the poll/sleep loop and, being `Arc`-shared, is
+// the thing `JoinHandle::cancel()` wakes from the other side — no waker here
+// ever points at thread-local storage, so it stays safe to wake after this
+// thread (and its `Exec`, if any) has gone away.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+fn execute<T, F: Future<Output = T>>(
+    mut f: F,
+    cancelled: &AtomicBool,
+    park: &Arc<Park>,
+) -> Option<T> {
+    // Unsafe: f can't move after this, because it is shadowed
+    #[allow(unsafe_code)]
+    let mut f = unsafe { Pin::new_unchecked(&mut f) };
+    // Get a waker and context for this executor.
+    let waker = waker_from(park.clone());
+    let context = &mut Context::from_waker(&waker);
+    // Run Future to completion, unless cancelled first.
+    loop {
+        // A `JoinHandle::cancel()` requested we stop polling.
+        if cancelled.load(Ordering::Acquire) {
+            break None;
+        }
+        // Exit with future output, on future completion, otherwise…
+        if let Poll::Ready(value) = f.as_mut().poll(context) {
+            break Some(value);
+        }
+        // Put the thread to sleep until wake() is called.
+        park.park();
+    }
+}
+
+// Executor data.
+#[cfg(any(target_arch = "wasm32", not(feature = "std")))]
+struct Exec {
+    // Pinned future.
+    tasks: RefCell<Vec<Task>>,
+
+    #[cfg(not(feature = "std"))]
+    // Set by `wake()` (possibly from interrupt context) to record that work
+    // is ready without reentrantly re-polling; observed and cleared by the
+    // `run()` loop.
+    pending: AtomicBool,
+
+    #[cfg(not(feature = "std"))]
+    // Hook `run()` calls to park the core when there's no pending work, set
+    // via `set_idle_hook()`.  Defaults to a no-op.
+    idle: Cell<fn()>,
+}
+
+#[cfg(any(target_arch = "wasm32", not(feature = "std")))]
+impl Exec {
+    fn new() -> Self {
+        Self {
+            tasks: RefCell::new(Vec::new()),
+            #[cfg(not(feature = "std"))]
+            pending: AtomicBool::new(false),
+            #[cfg(not(feature = "std"))]
+            idle: Cell::new(|| {}),
+        }
+    }
+
+    // Browsers drive the executor from their own event loop, so there's no
+    // interrupt-reentrancy concern: poll immediately, with the same single
+    // pass that `run()` loops over on the embedded, non-wasm no-std target.
+    #[cfg(all(target_arch = "wasm32", feature = "std"))]
+    fn wake(&self) {
+        self.poll_once();
+    }
+
+    // May be called from interrupt context on bare-metal targets, so it
+    // must not re-enter the poll loop; just record that work is pending and
+    // let `run()` notice it on its own schedule.
+    #[cfg(not(feature = "std"))]
     fn wake(&self) {
-        // Wake the task running on this thread - one pass through executor.
+        self.pending.store(true, Ordering::Release);
+    }
 
+    // One pass over the task queue, polling every task that isn't already
+    // finished.
+    #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
+    fn poll_once(&self) {
         // Get a waker and context for this executor.
         let waker = waker(self);
         let mut cx = Context::from_waker(&waker);
@@ -110,28 +385,6 @@ impl Exec {
         }
     }
 
-    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
-    #[allow(unsafe_code)]
-    fn execute<T, F: Future<Output = T>>(&mut self, mut f: F) -> T {
-        // Unsafe: f can't move after this, because it is shadowed
-        let mut f = unsafe { Pin::new_unchecked(&mut f) };
-        // Get a waker and context for this executor.
-        let waker = waker(self);
-        let context = &mut Context::from_waker(&waker);
-        // Run Future to completion.
-        loop {
-            // Exit with future output, on future completion, otherwise…
-            if let Poll::Ready(value) = f.as_mut().poll(context) {
-                break value;
-            }
-            // Put the thread to sleep until wake() is called.
-            let mut guard = self.mutex.lock().unwrap();
-            while !self.state.compare_and_swap(true, false, Ordering::SeqCst) {
-                guard = self.cvar.wait(guard).unwrap();
-            }
-        }
-    }
-
     // Find an open index in the tasks array.
     #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
     fn find_handle(&mut self) -> u32 {
@@ -144,25 +397,70 @@ impl Exec {
         self.tasks.borrow().len() as u32
     }
 
+    // Find `count` open indices in the tasks array in a single scan,
+    // reserving capacity up front for any that don't fit in an existing
+    // empty slot.  Used by `spawn_batch()` to avoid the O(n²) behavior of
+    // calling `find_handle()` once per task.
+    #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
+    fn find_handles(&mut self, count: usize) -> Vec<u32> {
+        let mut handles = Vec::with_capacity(count);
+
+        for (id, task) in self.tasks.borrow().iter().enumerate() {
+            if handles.len() == count {
+                break;
+            }
+            if let Task::Empty = task {
+                handles.push(id as u32);
+            }
+        }
+
+        let len = self.tasks.borrow().len();
+        let new_count = count - handles.len();
+        let mut tasks = self.tasks.borrow_mut();
+        tasks.reserve(new_count);
+
+        // `len + handles.len()` would double-count any reused `Empty` slots
+        // already in `handles`, skipping that many fresh indices; count the
+        // freshly appended ones separately instead.
+        for new_id in 0..new_count {
+            handles.push((len + new_id) as u32);
+        }
+
+        handles
+    }
+
     #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
     fn execute<F: Future<Output = ()>>(&mut self, handle: u32, f: F)
     where
         F: 'static,
     {
-        // Add to task queue
-        {
-            let mut tasks = self.tasks.borrow_mut();
-            tasks.resize_with(handle as usize + 1, || Task::Empty);
-            tasks[handle as usize] = Task::Future(Box::pin(f));
-        };
+        self.place(handle, f);
         // Begin Executor
         self.wake();
     }
+
+    // Add `f` to the task queue at `handle` without triggering a wake, so
+    // `spawn_batch()` can place every task before running the executor
+    // exactly once.
+    #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
+    fn place<F: Future<Output = ()>>(&mut self, handle: u32, f: F)
+    where
+        F: 'static,
+    {
+        let mut tasks = self.tasks.borrow_mut();
+        tasks.resize_with((handle as usize + 1).max(tasks.len()), || {
+            Task::Empty
+        });
+        tasks[handle as usize] = Task::Future(Box::pin(f));
+    }
 }
 
 // When the std library is available, use TLS so that multiple threads can
-// lazily initialize an executor.
-#[cfg(all(feature = "std"))]
+// lazily initialize an executor. Only needed on wasm32: the thread-per-task
+// desktop executor no longer touches `Exec` at all (see `execute()` above),
+// since a `Waker` pointing at thread-local storage can't safely outlive the
+// thread that owns it.
+#[cfg(all(target_arch = "wasm32", feature = "std"))]
 thread_local! {
     static EXEC: RefCell<Exec> = RefCell::new(Exec::new());
 }
@@ -175,8 +473,9 @@ static mut EXEC: Option<Exec> = None;
 ///
 /// On multi-threaded systems, this will start a new thread.  Similar to
 /// `futures::executor::block_on()`, except that doesn't block.  Similar to
-/// `std::thread::spawn()`, except that tasks don't detach, and will join on
-/// `Drop` (except when the **std** feature is not enabled, where it is expected
+/// `std::thread::spawn()`, except that tasks join on `Drop` unless
+/// [`JoinHandle::detach()`] or [`JoinHandle::cancel()`] is called first
+/// (except when the **std** feature is not enabled, where it is expected
 /// that you enter a "sleep" state).
 ///
 /// # Example
@@ -191,20 +490,56 @@ pub fn spawn<T, F: Future<Output = T>, G: Fn() -> F>(g: G) -> JoinHandle<T>
 where
     T: 'static + Send + Unpin,
     G: 'static + Send,
+{
+    spawn_with((), g)
+}
+
+/// Spawn a future, attaching arbitrary `meta`data to the resulting
+/// [`JoinHandle`], retrievable via [`JoinHandle::metadata()`].
+///
+/// Useful for labeling tasks for debugging, priority, or routing, so a
+/// completion handler can distinguish tasks by something more meaningful
+/// than a positional index.
+///
+/// # Example
+/// ```rust
+/// async fn async_main() {
+///     /* your code here */
+/// }
+///
+/// let task = pasts::executor::spawn_with("async_main", async_main);
+/// assert_eq!(*task.metadata(), "async_main");
+/// ```
+pub fn spawn_with<T, M, F: Future<Output = T>, G: Fn() -> F>(
+    meta: M,
+    g: G,
+) -> JoinHandle<T, M>
+where
+    T: 'static + Send + Unpin,
+    M: Send + 'static,
+    G: 'static + Send,
 {
     // Can start tasks on their own threads.
     #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
     {
-        let waker = Arc::new(Mutex::new((None, None)));
+        let output = Arc::new(OutputSlot::new());
+        let waker = Arc::new(AtomicWaker::new());
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let park = Arc::new(Park::new());
+        let task_cancelled = cancelled.clone();
+        let task_park = park.clone();
         JoinHandle {
+            output: output.clone(),
             waker: waker.clone(),
+            cancelled,
+            park,
+            meta,
             handle: Some(std::thread::spawn(move || {
-                let output = EXEC.with(|exec| exec.borrow_mut().execute(g()));
-                let mut waker = waker.lock().unwrap();
-                waker.0 = Some(output);
-                if let Some(waker) = waker.1.take() {
-                    waker.wake();
+                let value = execute(g(), &task_cancelled, &task_park);
+                if let Some(value) = value {
+                    output.set(value);
                 }
+                waker.wake();
             })),
         }
     }
@@ -231,19 +566,157 @@ where
                 });
                 handle
             },
+            meta,
             _phantom: PhantomData,
         }
     }
 }
 
+/// Spawn many futures at once, amortizing per-task overhead.
+///
+/// On the no-std/wasm executor, [`spawn()`] rescans the task queue for a
+/// free slot on every call (and on wasm also re-polls the whole queue),
+/// making N individual spawns quadratic in N. `spawn_batch()` reserves
+/// capacity and finds every task's slot in a single scan, then wakes the
+/// executor exactly once, so fanning out many tasks at startup stays linear.
+///
+/// # Example
+/// ```rust
+/// async fn worker(n: usize) {
+///     /* your code here */
+/// }
+///
+/// pasts::spawn_batch((0..8).map(|n| move || worker(n)));
+/// ```
+pub fn spawn_batch<T, F, G, I>(gs: I) -> alloc::vec::Vec<JoinHandle<T>>
+where
+    T: 'static + Send + Unpin,
+    F: Future<Output = T>,
+    G: Fn() -> F + 'static + Send,
+    I: IntoIterator<Item = G>,
+{
+    // No quadratic blow-up to amortize: each task already runs on its own
+    // thread, so batching is just spawning each one in turn.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    {
+        gs.into_iter().map(spawn).collect()
+    }
+
+    #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
+    #[allow(unsafe_code)]
+    unsafe {
+        let gs: Vec<G> = gs.into_iter().collect();
+        let exec = if let Some(ref mut exec) = EXEC {
+            exec
+        } else {
+            EXEC = Some(Exec::new());
+            EXEC.as_mut().unwrap()
+        };
+
+        let handles = exec.find_handles(gs.len());
+        let join_handles = handles
+            .iter()
+            .copied()
+            .zip(gs)
+            .map(|(handle, g)| {
+                exec.place(handle, async move {
+                    let output = g().await;
+                    let exec = EXEC.as_mut().unwrap();
+                    let mut tasks = exec.tasks.borrow_mut();
+                    let task = tasks.get_mut(handle as usize).unwrap();
+                    *task = Task::Output(Box::new(output));
+                });
+                JoinHandle {
+                    handle,
+                    meta: (),
+                    _phantom: PhantomData,
+                }
+            })
+            .collect();
+
+        // One pass over the whole queue for the entire batch, rather than
+        // one pass per task.
+        exec.wake();
+
+        join_handles
+    }
+}
+
+/// Set the hook [`run()`] calls to park the core when there's no pending
+/// work, e.g. an architecture `wfi`/`wfe` instruction wired up to your
+/// interrupt controller.  Defaults to a no-op (busy-poll).
+///
+/// Only available without the *`std`* feature.
+#[cfg(not(feature = "std"))]
+#[allow(unsafe_code)]
+pub fn set_idle_hook(idle: fn()) {
+    unsafe {
+        let exec = if let Some(ref mut exec) = EXEC {
+            exec
+        } else {
+            EXEC = Some(Exec::new());
+            EXEC.as_mut().unwrap()
+        };
+        exec.idle.set(idle);
+    }
+}
+
+/// Run the executor loop forever, fully idling the core between wakes.
+///
+/// Without *`std`*, a wake never polls tasks by itself; `run()` owns the
+/// loop that does: each iteration polls every pending
+/// task once, then — unless a waker already set the pending-work flag
+/// while polling — parks the core via the hook set with
+/// [`set_idle_hook()`]. Wakers triggered from interrupt context only set
+/// that flag, so they never reentrantly re-enter the poll loop; `run()`
+/// picks the work up on its next iteration instead.
+///
+/// Only available without the *`std`* feature.
+#[cfg(not(feature = "std"))]
+#[allow(unsafe_code)]
+pub fn run() -> ! {
+    let exec = unsafe {
+        if EXEC.is_none() {
+            EXEC = Some(Exec::new());
+        }
+        EXEC.as_mut().unwrap()
+    };
+
+    loop {
+        exec.poll_once();
+
+        if !exec.pending.swap(false, Ordering::AcqRel) {
+            (exec.idle.get())();
+        }
+    }
+}
+
 /// An owned permission to join on a task (`.await` on its termination).
+///
+/// The `M` parameter is arbitrary metadata attached via [`spawn_with()`],
+/// retrievable with [`JoinHandle::metadata()`]; it defaults to `()` for
+/// handles returned by [`spawn()`].
 #[derive(Debug)]
-pub struct JoinHandle<T>
+pub struct JoinHandle<T, M = ()>
 where
     T: Unpin,
 {
     #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
-    waker: Arc<Mutex<(Option<T>, Option<Waker>)>>,
+    output: Arc<OutputSlot<T>>,
+
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    waker: Arc<AtomicWaker>,
+
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    cancelled: Arc<AtomicBool>,
+
+    // Lets `cancel()` wake the task's executor out of the sleep it parks on
+    // between polls, so setting `cancelled` above actually takes effect
+    // promptly instead of waiting for some unrelated wake. `Arc`-shared
+    // rather than thread-local, so it's safe to wake even after the task's
+    // thread has finished and exited.
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    park: Arc<Park>,
 
     #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
     handle: Option<std::thread::JoinHandle<()>>,
@@ -253,26 +726,71 @@ where
 
     #[cfg(any(target_arch = "wasm32", not(feature = "std")))]
     _phantom: PhantomData<T>,
+
+    meta: M,
 }
 
 #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
-impl<T: Unpin> Drop for JoinHandle<T> {
+impl<T: Unpin, M> Drop for JoinHandle<T, M> {
     fn drop(&mut self) {
-        self.handle.take().unwrap().join().unwrap();
+        // `detach()`/`cancel()` already take `handle`, leaving nothing to
+        // join on a second drop.
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl<T: Unpin, M> JoinHandle<T, M> {
+    /// Let the task keep running to completion independently, dropping the
+    /// permission to join or cancel it.
+    pub fn detach(mut self) {
+        self.handle.take();
+    }
+
+    /// Request that the task stop being polled, and return its output if it
+    /// had already finished before the request could take effect.
+    pub fn cancel(mut self) -> Option<T> {
+        self.cancelled.store(true, Ordering::Release);
+        // The task may be parked, having last returned `Pending`; wake it so
+        // it re-checks `cancelled` instead of sleeping until some unrelated
+        // wake.
+        self.park.wake_by_ref();
+
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+
+        self.output.take()
     }
 }
 
-impl<T: Unpin + 'static> Future for JoinHandle<T> {
+impl<T: Unpin, M> JoinHandle<T, M> {
+    /// The metadata attached to this task when it was spawned with
+    /// [`spawn_with()`].
+    pub fn metadata(&self) -> &M {
+        &self.meta
+    }
+}
+
+impl<T: Unpin + 'static, M> Future for JoinHandle<T, M> {
     type Output = T;
 
     fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<T> {
         #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
         {
-            let mut waker = self.waker.lock().unwrap();
-            if let Some(output) = waker.0.take() {
+            if let Some(output) = self.output.take() {
+                return Poll::Ready(output);
+            }
+
+            self.waker.register(_cx.waker());
+
+            // The task may have finished between the first check and
+            // registering the waker; check again to avoid missing a wake.
+            if let Some(output) = self.output.take() {
                 Poll::Ready(output)
             } else {
-                waker.1 = Some(_cx.waker().clone());
                 Poll::Pending
             }
         }
@@ -299,7 +817,514 @@ impl<T: Unpin + 'static> Future for JoinHandle<T> {
     }
 }
 
-// Safe wrapper to create a `Waker`.
+/// Drives a group of [`JoinHandle`]s to completion together, surfacing each
+/// one's metadata alongside its output as it finishes.
+///
+/// Built via [`Join::new()`] from any collection of handles sharing the same
+/// `T` and `M`; `id` in [`Join::poll_on()`]'s callback is the handle's index
+/// within that collection. Lets a completion handler route by metadata
+/// without having to keep a `&JoinHandle` around just to call
+/// [`JoinHandle::metadata()`] again.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+pub struct Join<T: Unpin, M = ()> {
+    handles: Vec<Option<JoinHandle<T, M>>>,
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl<T: Unpin + 'static, M> Join<T, M> {
+    /// Group `handles` together so they can be polled and completed as a
+    /// unit via [`Join::poll_on()`].
+    pub fn new(handles: impl IntoIterator<Item = JoinHandle<T, M>>) -> Self {
+        Self {
+            handles: handles.into_iter().map(Some).collect(),
+        }
+    }
+
+    /// Poll every still-running handle once, calling `on_done` with
+    /// `(id, meta, value)` for each one that completes on this poll, where
+    /// `id` is the handle's index within the group passed to
+    /// [`Join::new()`] and `meta` is its [`JoinHandle::metadata()`].
+    ///
+    /// Resolves to `Poll::Ready(())` once every handle in the group has
+    /// completed; until then, resolves to `Poll::Pending`, having already
+    /// called `on_done` for anything that finished this poll.
+    pub fn poll_on<F: FnMut(usize, &M, T)>(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut on_done: F,
+    ) -> Poll<()> {
+        let mut all_done = true;
+
+        for (id, slot) in self.handles.iter_mut().enumerate() {
+            let handle = match slot {
+                Some(handle) => handle,
+                None => continue,
+            };
+
+            // Safety: `handle` is owned, not moved out of, and `JoinHandle`
+            // doesn't require structural pinning.
+            #[allow(unsafe_code)]
+            let poll = unsafe { Pin::new_unchecked(handle) }.poll(cx);
+
+            match poll {
+                Poll::Ready(value) => {
+                    on_done(id, &slot.as_ref().unwrap().meta, value);
+                    // Drop the handle now it's done, same as removing it
+                    // from the group; `JoinHandle::drop()` is a no-op join
+                    // here since the task already finished.
+                    *slot = None;
+                }
+                Poll::Pending => all_done = false,
+            }
+        }
+
+        if all_done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// An owned permission to join a task spawned within a [`Scope`].
+///
+/// Unlike [`JoinHandle`], dropping a `ScopedJoinHandle` doesn't join the
+/// task, since [`scope()`] already guarantees every scoped task has
+/// completed by the time it returns.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+pub struct ScopedJoinHandle<'scope, T> {
+    output: Arc<OutputSlot<T>>,
+    waker: Arc<AtomicWaker>,
+    _scope: PhantomData<&'scope ()>,
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl<T: Unpin> Future for ScopedJoinHandle<'_, T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        if let Some(output) = self.output.take() {
+            return Poll::Ready(output);
+        }
+
+        self.waker.register(cx.waker());
+
+        match self.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// A scope within which tasks may borrow data owned by the caller.
+///
+/// Tasks spawned through a `Scope` aren't required to be `'static`, because
+/// [`scope()`] blocks until every task spawned within it has completed
+/// before returning, so no reference they borrow can escape.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+pub struct Scope<'scope> {
+    handles: &'scope Mutex<Vec<std::thread::JoinHandle<()>>>,
+    _scope: PhantomData<&'scope mut &'scope ()>,
+}
+
+// A sibling of `Scope` holding the actual handle storage, kept separate so
+// its `Drop` impl isn't parameterized over `'scope`: a destructor on
+// `Scope<'scope>` itself would force `'scope` to be considered live for the
+// duration of the drop, which conflicts with `'scope` ending the moment
+// `scope()`'s closure returns. Declaring this as a plain local that outlives
+// `scope` joins every spawned thread on the way out, whether `scope()`
+// returns normally or unwinds out of the caller's closure; without this, a
+// panic would skip straight past the join loop and leave scoped threads
+// still running against freed stack data.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+struct ScopeGuard(Mutex<Vec<std::thread::JoinHandle<()>>>);
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        for handle in self.0.get_mut().unwrap().drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+impl<'scope> Scope<'scope> {
+    /// Spawn a future that may borrow data owned outside the scope, so long
+    /// as it outlives `'scope`.
+    #[allow(unsafe_code)]
+    pub fn spawn<T, F>(&self, f: F) -> ScopedJoinHandle<'scope, T>
+    where
+        F: Future<Output = T> + Send + 'scope,
+        T: Send + Unpin + 'scope,
+    {
+        let output = Arc::new(OutputSlot::new());
+        let waker = Arc::new(AtomicWaker::new());
+
+        let task_output = output.clone();
+        let task_waker = waker.clone();
+
+        let run: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+            // Scoped tasks have no `cancel()`, so this flag never flips and
+            // `park` is only ever woken by the task's own waker; `execute()`
+            // still requires both.
+            let cancelled = AtomicBool::new(false);
+            let park = Arc::new(Park::new());
+            let value = execute(f, &cancelled, &park);
+
+            if let Some(value) = value {
+                task_output.set(value);
+            }
+
+            task_waker.wake();
+        });
+
+        // Safety: `scope()`'s `ScopeGuard` joins every handle pushed to
+        // `self.handles` before it goes away, whether `scope()` returns
+        // normally or unwinds, so this thread (and anything `f` borrows)
+        // cannot outlive `'scope`, even though the closure's type is
+        // erased to `'static` to satisfy `std::thread::spawn()`.
+        let run: Box<dyn FnOnce() + Send + 'static> =
+            unsafe { core::mem::transmute(run) };
+
+        self.handles.lock().unwrap().push(std::thread::spawn(run));
+
+        ScopedJoinHandle {
+            output,
+            waker,
+            _scope: PhantomData,
+        }
+    }
+}
+
+/// Create a scope for spawning tasks that may borrow local data, blocking
+/// until every task spawned within it has completed.
+///
+/// Mirrors [`std::thread::scope()`], but for `pasts` tasks rather than bare
+/// threads:
+///
+/// ```rust,no_run
+/// let local = 5;
+///
+/// pasts::executor::scope(|scope| {
+///     scope.spawn(async {
+///         println!("borrowed {local}");
+///     });
+/// });
+/// ```
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub fn scope<F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope>) -> T,
+{
+    let guard = ScopeGuard(Mutex::new(Vec::new()));
+    let scope = Scope {
+        handles: &guard.0,
+        _scope: PhantomData,
+    };
+
+    // `guard`'s `Drop` impl joins every spawned thread when it goes out of
+    // scope below, whether `f` returns normally or panics; it's declared
+    // before `scope`, so (per reverse drop order) it outlives the borrow and
+    // only runs its join loop after `scope` itself has dropped.
+    f(&scope)
+}
+
+/// A [`Stream`] that drives a spawned task and yields its output exactly
+/// once, then terminates.
+///
+/// Obtained by spawning a future with [`spawn_stream()`].  Requires the
+/// *`stream`* feature.
+#[cfg(all(feature = "stream", feature = "std", not(target_arch = "wasm32")))]
+#[derive(Debug)]
+pub struct JoinStream<T: Unpin>(Option<JoinHandle<T>>);
+
+#[cfg(all(feature = "stream", feature = "std", not(target_arch = "wasm32")))]
+impl<T: Unpin + 'static> Stream for JoinStream<T> {
+    type Item = T;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<T>> {
+        let handle = match self.0.as_mut() {
+            Some(handle) => handle,
+            None => return Poll::Ready(None),
+        };
+
+        // Safety: `handle` is owned, not moved out of, and `JoinHandle`
+        // doesn't require structural pinning.
+        #[allow(unsafe_code)]
+        match unsafe { Pin::new_unchecked(handle) }.poll(cx) {
+            Poll::Ready(output) => {
+                self.0 = None;
+                Poll::Ready(Some(output))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Spawn a future and expose its eventual output as a one-item [`Stream`],
+/// for interop with `Stream`-based combinators.
+///
+/// Requires the *`stream`* feature.
+#[cfg(all(feature = "stream", feature = "std", not(target_arch = "wasm32")))]
+pub fn spawn_stream<T, F: Future<Output = T>, G: Fn() -> F>(
+    g: G,
+) -> JoinStream<T>
+where
+    T: 'static + Send + Unpin,
+    G: 'static + Send,
+{
+    JoinStream(Some(spawn(g)))
+}
+
+/// Adapts a [`Stream`] into a [`Future`], the inverse of [`JoinStream`]: the
+/// returned future calls `on_item` for each item the stream yields, then
+/// resolves to `on_end()`'s return value once the stream ends.
+///
+/// Wrap the result in [`spawn()`] to run a stream to completion as a task,
+/// since a spawned task produces a single output rather than a sequence of
+/// them.
+///
+/// Requires the *`stream`* feature.
+#[cfg(feature = "stream")]
+#[derive(Debug)]
+pub struct StreamTask<S, F, E> {
+    stream: S,
+    on_item: F,
+    on_end: Option<E>,
+}
+
+#[cfg(feature = "stream")]
+impl<S, F, E> StreamTask<S, F, E> {
+    /// Create a task future that drives `stream` to completion.
+    pub fn new(stream: S, on_item: F, on_end: E) -> Self {
+        Self {
+            stream,
+            on_item,
+            on_end: Some(on_end),
+        }
+    }
+}
+
+#[cfg(feature = "stream")]
+impl<S, T, F, E> Future for StreamTask<S, F, E>
+where
+    S: Stream + Unpin,
+    F: FnMut(S::Item) + Unpin,
+    E: FnOnce() -> T + Unpin,
+{
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let this = self.get_mut();
+
+        loop {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) => (this.on_item)(item),
+                Poll::Ready(None) => {
+                    let on_end = this
+                        .on_end
+                        .take()
+                        .expect("StreamTask polled after completion");
+
+                    break Poll::Ready(on_end());
+                }
+                Poll::Pending => break Poll::Pending,
+            }
+        }
+    }
+}
+
+/// A repeatedly-notifying event source, analogous to a [`Stream`] whose
+/// items never stop: each poll yields the next event, with no terminal
+/// state of its own.
+///
+/// See [`from_stream()`] to build one from a [`Stream`] that explicitly
+/// handles the stream's end, and [`Notifier::into_stream()`] to go the
+/// other way.
+///
+/// Requires the *`stream`* feature.
+#[cfg(feature = "stream")]
+pub trait Notifier {
+    /// The event type yielded by each notification.
+    type Event;
+
+    /// Poll for the next event.
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>)
+        -> Poll<Self::Event>;
+
+    /// Expose this `Notifier` as a never-ending [`Stream`]: since a
+    /// `Notifier` has no terminal state, the returned stream never yields
+    /// `None`.
+    fn into_stream(self) -> NotifierStream<Self>
+    where
+        Self: Sized,
+    {
+        NotifierStream(self)
+    }
+}
+
+/// A never-ending [`Stream`] wrapping a [`Notifier`]; see
+/// [`Notifier::into_stream()`].
+///
+/// Requires the *`stream`* feature.
+#[cfg(feature = "stream")]
+#[derive(Debug)]
+pub struct NotifierStream<N>(N);
+
+#[cfg(feature = "stream")]
+impl<N: Notifier + Unpin> Stream for NotifierStream<N> {
+    type Item = N::Event;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<N::Event>> {
+        let this = self.get_mut();
+
+        Pin::new(&mut this.0).poll_next(cx).map(Some)
+    }
+}
+
+/// A [`Notifier`] wrapping a [`Stream`], explicitly handling the stream's
+/// end since a `Notifier` is expected to always make progress; see
+/// [`from_stream()`].
+///
+/// Once `stream` ends, every subsequent poll calls `on_end` again rather
+/// than re-polling the exhausted stream, "fusing" it to a steady final
+/// event.
+///
+/// Requires the *`stream`* feature.
+#[cfg(feature = "stream")]
+#[derive(Debug)]
+pub struct StreamNotifier<S, E> {
+    stream: S,
+    on_end: E,
+    done: bool,
+}
+
+#[cfg(feature = "stream")]
+impl<S, T, E> Notifier for StreamNotifier<S, E>
+where
+    S: Stream<Item = T> + Unpin,
+    E: FnMut() -> T + Unpin,
+{
+    type Event = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let this = self.get_mut();
+
+        if this.done {
+            return Poll::Ready((this.on_end)());
+        }
+
+        match Pin::new(&mut this.stream).poll_next(cx) {
+            Poll::Ready(Some(item)) => Poll::Ready(item),
+            Poll::Ready(None) => {
+                this.done = true;
+                Poll::Ready((this.on_end)())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Wrap `stream` as a [`Notifier`], calling `on_end` in place of the
+/// stream's end, and again on every poll thereafter.
+///
+/// Requires the *`stream`* feature.
+#[cfg(feature = "stream")]
+pub fn from_stream<S, T, E>(stream: S, on_end: E) -> StreamNotifier<S, E>
+where
+    S: Stream<Item = T> + Unpin,
+    E: FnMut() -> T + Unpin,
+{
+    StreamNotifier {
+        stream,
+        on_end,
+        done: false,
+    }
+}
+
+/// A safe alternative to hand-writing a `RawWaker`/`RawWakerVTable` for
+/// building a custom [`Waker`], e.g. for a custom `Notifier` or an
+/// alternate wake strategy (like pushing a task id into a ready queue).
+///
+/// Implement this for a type shared via [`Arc`], then turn it into a
+/// [`Waker`] with [`waker_from()`].
+pub trait Wake {
+    /// Wake the task, consuming the `Arc`.
+    ///
+    /// The default implementation delegates to [`Wake::wake_by_ref()`] and
+    /// then drops `self`; override it when consuming `self` lets you wake
+    /// more efficiently.
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    /// Wake the task without consuming the `Arc`.
+    fn wake_by_ref(self: &Arc<Self>);
+}
+
+/// Build a [`Waker`] from an `Arc<W>` where `W: Wake`, without writing any
+/// `unsafe` code at the call site.
+#[allow(unsafe_code)]
+pub fn waker_from<W>(wake: Arc<W>) -> Waker
+where
+    W: Wake + Send + Sync + 'static,
+{
+    unsafe fn clone_raw<W: Wake + Send + Sync + 'static>(
+        data: *const (),
+    ) -> RawWaker {
+        let arc = Arc::from_raw(data.cast::<W>());
+        let cloned = Arc::into_raw(arc.clone()).cast::<()>();
+        core::mem::forget(arc);
+        RawWaker::new(cloned, vtable::<W>())
+    }
+
+    unsafe fn wake_raw<W: Wake + Send + Sync + 'static>(data: *const ()) {
+        Wake::wake(Arc::from_raw(data.cast::<W>()));
+    }
+
+    unsafe fn wake_by_ref_raw<W: Wake + Send + Sync + 'static>(
+        data: *const (),
+    ) {
+        let arc = Arc::from_raw(data.cast::<W>());
+        Wake::wake_by_ref(&arc);
+        core::mem::forget(arc);
+    }
+
+    unsafe fn drop_raw<W: Wake + Send + Sync + 'static>(data: *const ()) {
+        drop(Arc::from_raw(data.cast::<W>()));
+    }
+
+    // A `const` item can't name the outer `W`, so the vtable is built in a
+    // generic function and promoted to `'static` instead.
+    fn vtable<W: Wake + Send + Sync + 'static>() -> &'static RawWakerVTable {
+        &RawWakerVTable::new(
+            clone_raw::<W>,
+            wake_raw::<W>,
+            wake_by_ref_raw::<W>,
+            drop_raw::<W>,
+        )
+    }
+
+    let data = Arc::into_raw(wake).cast::<()>();
+
+    unsafe { Waker::from_raw(RawWaker::new(data, vtable::<W>())) }
+}
+
+// Safe wrapper to create a `Waker` for an `Exec`.  Kept private and built
+// by hand (rather than on top of `waker_from()`) since `Exec` isn't
+// refcounted: callers only ever hold a borrow of it, never an `Arc`.
+#[cfg(any(target_arch = "wasm32", not(feature = "std")))]
 #[inline]
 #[allow(unsafe_code)]
 fn waker(exec: *const Exec) -> Waker {
@@ -319,3 +1344,23 @@ fn waker(exec: *const Exec) -> Waker {
 
     unsafe { Waker::from_raw(RawWaker::new(exec.cast(), &RWVT)) }
 }
+
+#[cfg(all(test, feature = "std", not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    // Regression test for a use-after-free: `cancel()` used to wake a
+    // `Waker` built from the task's thread's thread-local `Exec`, which is
+    // torn down as soon as that thread exits. Calling `cancel()` well after
+    // the task (and its thread) has finished exercises exactly that path.
+    #[test]
+    fn cancel_after_completion() {
+        let handle = spawn(|| async { 42 });
+
+        // Give the task's thread time to run to completion and exit before
+        // `cancel()` tries to wake it.
+        std::thread::sleep(std::time::Duration::from_millis(100));
+
+        assert_eq!(handle.cancel(), Some(42));
+    }
+}